@@ -0,0 +1,143 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+
+/// A throwaway repository with one commit, and a home directory without a
+/// gy config so the user's own settings stay out of it.
+fn test_repo() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    git(dir.path(), &["init", "-q"]);
+    fs::write(dir.path().join("README"), "hello\n").unwrap();
+    git(dir.path(), &["add", "README"]);
+    git(dir.path(), &["commit", "-q", "-m", "init"]);
+    dir
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=gy", "-c", "user.email=gy@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Runs gy against a server that isn't there, with the request body logged,
+/// so the test sees exactly what would have been sent.
+fn gy_request(dir: &Path, home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gy"))
+        .args([
+            "-vv",
+            "--provider",
+            "ollama",
+            "--base-url",
+            "http://127.0.0.1:9",
+        ])
+        .args(["--retries", "0", "--no-stream", "--no-cache", "--dry-run"])
+        .args(args)
+        .current_dir(dir)
+        .env("HOME", home)
+        .env_remove("ANTHROPIC_API_KEY")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn exclude_from_a_subdirectory_keeps_the_rest_of_the_commit() {
+    let repo = test_repo();
+    let home = tempfile::tempdir().unwrap();
+    let sub = repo.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(repo.path().join("top.txt"), "top\n").unwrap();
+    fs::write(sub.join("inner.txt"), "inner\n").unwrap();
+    fs::write(repo.path().join("Cargo.lock"), "lock\n").unwrap();
+    git(repo.path(), &["add", "."]);
+
+    let output = gy_request(&sub, home.path(), &["--exclude", "*.lock"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let body = stderr
+        .lines()
+        .find(|line| line.contains("Request body:"))
+        .unwrap_or_else(|| panic!("no request was made:\n{}", stderr));
+
+    assert!(body.contains("diff --git a/top.txt b/top.txt"), "{}", body);
+    assert!(
+        body.contains("diff --git a/sub/inner.txt b/sub/inner.txt"),
+        "{}",
+        body
+    );
+    assert!(!body.contains("diff --git a/Cargo.lock"), "{}", body);
+}
+
+/// Serves `reply` as an Ollama chat response to every request and returns
+/// the base URL to reach it at.
+fn serve_reply(reply: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let body = serde_json::json!({
+        "message": { "role": "assistant", "content": reply },
+        "done": true,
+    })
+    .to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(&stream);
+            let mut length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap_or(0);
+                }
+                line.clear();
+            }
+            let mut request = vec![0; length];
+            let _ = reader.read_exact(&mut request);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    url
+}
+
+#[test]
+fn commit_keeps_a_message_that_looks_like_a_flag() {
+    let repo = test_repo();
+    let home = tempfile::tempdir().unwrap();
+    fs::write(repo.path().join("notes.txt"), "notes\n").unwrap();
+    git(repo.path(), &["add", "notes.txt"]);
+    let message = "--amend the \"notes\" with `ticks` and $HOME";
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gy"))
+        .args(["--provider", "ollama", "--base-url", &serve_reply(message)])
+        .args(["--retries", "0", "--no-stream", "--no-cache"])
+        .args(["--style", "plain", "--yes", "--force"])
+        .current_dir(repo.path())
+        .env("HOME", home.path())
+        .env("GIT_AUTHOR_NAME", "gy")
+        .env("GIT_AUTHOR_EMAIL", "gy@example.com")
+        .env("GIT_COMMITTER_NAME", "gy")
+        .env("GIT_COMMITTER_EMAIL", "gy@example.com")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let log = Command::new("git")
+        .args(["log", "-1", "--format=%B"])
+        .current_dir(repo.path())
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&log.stdout).trim(), message);
+}